@@ -9,7 +9,7 @@ fn main() {
             eprintln!("New instance, listening for messages");
             loop {
                 if let Some(mut conn) = listener.accept() {
-                    dbg!(conn.recv());
+                    dbg!(conn.recv().unwrap());
                 }
             }
         }
@@ -21,7 +21,7 @@ fn main() {
                 Some(arg) => Msg::String(arg.to_string()),
                 None => Msg::Nudge,
             };
-            stream.send(payload);
+            stream.send(payload).unwrap();
         }
     }
 }
@@ -3,6 +3,10 @@
 //!
 //! This library deliberately aims to be simple and lightweight, so it **only supports
 //! a single existing instance**.
+//!
+//! [`Listener::as_raw_fd`]/[`Listener::as_raw_handle`] expose the underlying
+//! socket so an application that already drives its own event loop can
+//! register it for readiness there instead of polling on a dedicated thread.
 
 #![warn(missing_docs)]
 
@@ -14,6 +18,7 @@ use {
     },
     std::{
         io::{Read, Write},
+        net::{TcpListener, TcpStream},
         time::{Duration, Instant},
     },
 };
@@ -28,8 +33,22 @@ pub enum Endpoint {
     Existing(Stream),
 }
 
+/// The underlying socket a [`Listener`] is built on. Streams accepted from
+/// either variant speak the same [`Msg`] framing, so callers never need to
+/// match on this.
+enum RawListener {
+    Local(local_socket::Listener),
+    Tcp(TcpListener),
+}
+
+/// The underlying socket a [`Stream`] is built on. See [`RawListener`].
+enum RawStream {
+    Local(local_socket::Stream),
+    Tcp(TcpStream),
+}
+
 /// IPC listener to listen to incoming connections
-pub struct Listener(local_socket::Listener);
+pub struct Listener(RawListener);
 
 impl Listener {
     /// Accept an incoming connection.
@@ -38,14 +57,47 @@ impl Listener {
     /// This is sufficient if you just want to do something like focus a window, if there
     /// was an attempted connection by a new instance.
     pub fn accept(&self) -> Option<Stream> {
-        match self.0.accept() {
-            Ok(stream) => Some(Stream(stream)),
+        let result = match &self.0 {
+            RawListener::Local(listener) => listener.accept().map(RawStream::Local),
+            RawListener::Tcp(listener) => listener
+                .accept()
+                .map(|(stream, _addr)| RawStream::Tcp(stream)),
+        };
+        match result {
+            Ok(stream) => Some(Stream::new(stream)),
             Err(e) => {
                 log::error!("{e:?}");
                 None
             }
         }
     }
+
+    /// Returns the underlying socket's file descriptor, for registering it
+    /// with an external event loop instead of spinning a thread around
+    /// `wait_to_be_new`-style polling.
+    #[cfg(unix)]
+    pub fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        match &self.0 {
+            RawListener::Local(local_socket::Listener::UdSocket(listener)) => {
+                std::os::fd::AsRawFd::as_raw_fd(&std::os::fd::AsFd::as_fd(listener))
+            }
+            RawListener::Tcp(listener) => std::os::fd::AsRawFd::as_raw_fd(listener),
+        }
+    }
+    /// Returns the underlying socket's handle, for registering it with an
+    /// external event loop instead of spinning a thread around
+    /// `wait_to_be_new`-style polling.
+    #[cfg(windows)]
+    pub fn as_raw_handle(&self) -> std::os::windows::io::RawHandle {
+        match &self.0 {
+            RawListener::Local(local_socket::Listener::NamedPipe(listener)) => {
+                std::os::windows::io::AsRawHandle::as_raw_handle(listener.inner())
+            }
+            RawListener::Tcp(listener) => {
+                std::os::windows::io::AsRawHandle::as_raw_handle(listener)
+            }
+        }
+    }
 }
 
 /// Message between two processes
@@ -60,106 +112,331 @@ pub enum Msg {
     String(String),
     /// Empty payload
     Nudge,
+    /// A command line's worth of arguments, e.g. `std::env::args()`
+    ///
+    /// Arguments are `String`s, so non-UTF-8 argv bytes (e.g. from
+    /// `std::env::args_os()` on Unix) cannot be carried: [`forward_args`]
+    /// replaces them with `to_string_lossy` rather than preserving them.
+    Args(Vec<String>),
 }
 
-fn write_u8(num: u8, stream: &mut local_socket::Stream) -> std::io::Result<()> {
+/// Fixed 4-byte sequence prefixed to every frame, used to resynchronize the
+/// stream after a corrupted or unrecognized frame.
+const SYNC_MAGIC: [u8; 4] = [0xE1, 0x51, 0xA1, 0xCE];
+
+fn write_u8(num: u8, stream: &mut RawStream) -> std::io::Result<()> {
     stream.write_all(std::slice::from_ref(&num))
 }
 
-fn read_u8(stream: &mut local_socket::Stream) -> std::io::Result<u8> {
+fn read_u8(stream: &mut RawStream) -> std::io::Result<u8> {
     let mut num: u8 = 0;
     stream.read_exact(std::slice::from_mut(&mut num))?;
     Ok(num)
 }
 
-fn write_usize(num: usize, stream: &mut local_socket::Stream) -> std::io::Result<()> {
-    let bytes = num.to_le_bytes();
-    stream.write_all(&bytes)
+fn write_usize(num: usize, stream: &mut RawStream) -> std::io::Result<()> {
+    stream.write_all(&num.to_le_bytes())
 }
 
-fn read_usize(stream: &mut local_socket::Stream) -> std::io::Result<usize> {
+fn read_usize(stream: &mut RawStream) -> std::io::Result<usize> {
     let mut buf = [0; std::mem::size_of::<usize>()];
     stream.read_exact(&mut buf)?;
     Ok(usize::from_le_bytes(buf))
 }
 
-fn read_vec(stream: &mut local_socket::Stream) -> std::io::Result<Vec<u8>> {
+fn read_vec(stream: &mut RawStream, max_msg_len: usize) -> std::io::Result<Vec<u8>> {
     let len = read_usize(stream)?;
     log::debug!("read_vec: length: {len}");
+    if len > max_msg_len {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("message length {len} exceeds max_msg_len {max_msg_len}"),
+        ));
+    }
     let mut buf = vec![0; len];
     stream.read_exact(&mut buf)?;
     Ok(buf)
 }
 
+fn write_strings(strings: &[String], stream: &mut RawStream) -> std::io::Result<()> {
+    write_usize(strings.len(), stream)?;
+    for s in strings {
+        write_usize(s.len(), stream)?;
+        stream.write_all(s.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_strings(stream: &mut RawStream, max_msg_len: usize) -> std::io::Result<Vec<String>> {
+    let count = read_usize(stream)?;
+    if count > max_msg_len {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("argument count {count} exceeds max_msg_len {max_msg_len}"),
+        ));
+    }
+    let mut strings = Vec::with_capacity(count.min(1024));
+    for _ in 0..count {
+        let bytes = read_vec(stream, max_msg_len)?;
+        strings.push(String::from_utf8_lossy(&bytes).into_owned());
+    }
+    Ok(strings)
+}
+
+impl Read for RawStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            RawStream::Local(stream) => stream.read(buf),
+            RawStream::Tcp(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for RawStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            RawStream::Local(stream) => stream.write(buf),
+            RawStream::Tcp(stream) => stream.write(buf),
+        }
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            RawStream::Local(stream) => stream.flush(),
+            RawStream::Tcp(stream) => stream.flush(),
+        }
+    }
+}
+
+impl RawStream {
+    /// Set the timeout for individual read syscalls on this socket. `None`
+    /// means block indefinitely.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            RawStream::Local(stream) => stream.set_recv_timeout(timeout),
+            RawStream::Tcp(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+    /// Set the timeout for individual write syscalls on this socket. `None`
+    /// means block indefinitely.
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            RawStream::Local(stream) => stream.set_send_timeout(timeout),
+            RawStream::Tcp(stream) => stream.set_write_timeout(timeout),
+        }
+    }
+}
+
+/// Discard bytes from `stream` one at a time until the next occurrence of
+/// [`SYNC_MAGIC`] is found, leaving the stream positioned right after it.
+///
+/// This is how [`Msg::read`] recovers from a garbled or unrecognized frame
+/// instead of leaving the stream permanently desynchronized.
+fn resync(stream: &mut RawStream) -> std::io::Result<()> {
+    let mut window = [0u8; SYNC_MAGIC.len()];
+    stream.read_exact(&mut window)?;
+    loop {
+        if window == SYNC_MAGIC {
+            return Ok(());
+        }
+        window.rotate_left(1);
+        let last = window.last_mut().expect("SYNC_MAGIC is non-empty");
+        *last = read_u8(stream)?;
+    }
+}
+
 impl Msg {
     const fn discriminant(&self) -> u8 {
         unsafe { *(self as *const Self as *const u8) }
     }
-    fn write(self, stream: &mut local_socket::Stream) {
+    fn write(self, stream: &mut RawStream, req_id: RequestId) -> std::io::Result<()> {
         let discriminant = self.discriminant();
         log::debug!("Writing discriminant {discriminant}");
-        write_u8(discriminant, stream).unwrap();
+        stream.write_all(&SYNC_MAGIC)?;
+        write_usize(req_id, stream)?;
+        write_u8(discriminant, stream)?;
         match self {
             Msg::Num(n) => {
-                write_usize(n, stream).unwrap();
+                write_usize(n, stream)?;
             }
             Msg::Bytes(bytes) => {
-                write_usize(bytes.len(), stream).unwrap();
+                write_usize(bytes.len(), stream)?;
                 log::debug!("Wrote byte length: {}", bytes.len());
-                stream.write_all(&bytes).unwrap();
+                stream.write_all(&bytes)?;
             }
             Msg::String(str) => {
-                write_usize(str.len(), stream).unwrap();
+                write_usize(str.len(), stream)?;
                 log::debug!("Wrote byte length: {}", str.len());
-                stream.write_all(str.as_bytes()).unwrap();
+                stream.write_all(str.as_bytes())?;
             }
             Msg::Nudge => {}
+            Msg::Args(args) => {
+                write_strings(&args, stream)?;
+            }
         }
+        Ok(())
     }
-    fn read(stream: &mut local_socket::Stream) -> std::io::Result<Self> {
-        let discriminant = read_u8(stream)?;
-        log::debug!("Read discriminant {discriminant}");
-        match discriminant {
-            0 => Ok(Self::Num(read_usize(stream)?)),
-            1 => Ok(Self::Bytes(read_vec(stream)?)),
-            2 => {
-                log::debug!("Reading string...");
-                let bytes = read_vec(stream)?;
-                Ok(Self::String(String::from_utf8_lossy(&bytes).into_owned()))
+    /// Read a single frame, resynchronizing on the magic sequence if the
+    /// discriminant is unrecognized or the declared length exceeds
+    /// `max_msg_len`. Returns the frame's request id alongside the message.
+    fn read(stream: &mut RawStream, max_msg_len: usize) -> std::io::Result<(RequestId, Self)> {
+        loop {
+            resync(stream)?;
+            let req_id = read_usize(stream)?;
+            let discriminant = read_u8(stream)?;
+            log::debug!("Read discriminant {discriminant}");
+            let msg = match discriminant {
+                0 => read_usize(stream).map(Self::Num),
+                1 => read_vec(stream, max_msg_len).map(Self::Bytes),
+                2 => {
+                    log::debug!("Reading string...");
+                    read_vec(stream, max_msg_len)
+                        .map(|bytes| Self::String(String::from_utf8_lossy(&bytes).into_owned()))
+                }
+                3 => Ok(Self::Nudge),
+                4 => read_strings(stream, max_msg_len).map(Self::Args),
+                etc => {
+                    log::error!("Unknown message discriminant {etc}, resynchronizing");
+                    continue;
+                }
+            };
+            match msg {
+                Ok(msg) => return Ok((req_id, msg)),
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    log::error!("{e}, resynchronizing");
+                    continue;
+                }
+                Err(e) => return Err(e),
             }
-            3 => Ok(Self::Nudge),
-            etc => panic!("Unknown message discriminant {etc}"),
         }
     }
 }
 
+/// Default cap on the size of a single `Bytes`/`String` payload, used unless
+/// overridden with [`Stream::set_max_msg_len`].
+const DEFAULT_MAX_MSG_LEN: usize = 64 * 1024 * 1024;
+
+/// Identifier correlating a [`Stream::request`] with the [`Stream::reply`]
+/// sent back for it. `0` is reserved for fire-and-forget [`Stream::send`]
+/// frames, which no reply is expected for.
+pub type RequestId = usize;
+
 /// IPC message stream with a simple protocol
-pub struct Stream(local_socket::Stream);
+pub struct Stream {
+    inner: RawStream,
+    max_msg_len: usize,
+    next_req_id: RequestId,
+}
 
 impl Stream {
+    fn new(inner: RawStream) -> Self {
+        Self {
+            inner,
+            max_msg_len: DEFAULT_MAX_MSG_LEN,
+            next_req_id: 1,
+        }
+    }
+    /// Set the maximum allowed length, in bytes, of a single `Bytes`/`String`
+    /// payload. Frames claiming to be larger are rejected and the stream is
+    /// resynchronized, rather than allocating a buffer of the claimed size.
+    pub fn set_max_msg_len(&mut self, max_msg_len: usize) {
+        self.max_msg_len = max_msg_len;
+    }
+    /// Set the timeout for [`Stream::recv`]. `None` means block indefinitely,
+    /// mirroring `std::net::TcpStream::set_read_timeout`. Implemented over
+    /// the underlying socket's own read timeout, so it bounds each
+    /// individual read syscall rather than the whole frame.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+    /// Set the timeout for [`Stream::send`]. `None` means block indefinitely,
+    /// mirroring `std::net::TcpStream::set_write_timeout`. Implemented over
+    /// the underlying socket's own write timeout, so it bounds each
+    /// individual write syscall rather than the whole frame.
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.inner.set_write_timeout(timeout)
+    }
     /// Send a message to the recipient
-    pub fn send(&mut self, msg: Msg) {
-        msg.write(&mut self.0)
+    pub fn send(&mut self, msg: Msg) -> std::io::Result<()> {
+        msg.write(&mut self.inner, 0)
     }
     /// Receive a message, if any
-    pub fn recv(&mut self) -> Option<Msg> {
-        match Msg::read(&mut self.0) {
-            Ok(msg) => Some(msg),
+    pub fn recv(&mut self) -> std::io::Result<Option<Msg>> {
+        match Msg::read(&mut self.inner, self.max_msg_len) {
+            Ok((_req_id, msg)) => Ok(Some(msg)),
+            Err(e) if is_no_message(e.kind()) => Ok(None),
             Err(e) => {
                 log::error!("Stream::recv error: {e}");
-                None
+                Err(e)
             }
         }
     }
+    /// Send `msg` and block (respecting the read timeout) for the reply sent
+    /// back with [`Stream::reply`].
+    pub fn request(&mut self, msg: Msg) -> std::io::Result<Msg> {
+        let req_id = self.next_req_id;
+        self.next_req_id += 1;
+        msg.write(&mut self.inner, req_id)?;
+        loop {
+            let (reply_id, reply) = Msg::read(&mut self.inner, self.max_msg_len)?;
+            if reply_id == req_id {
+                return Ok(reply);
+            }
+            log::debug!("request {req_id}: discarding reply for unrelated request {reply_id}");
+        }
+    }
+    /// Receive a request sent with [`Stream::request`], if any. The returned
+    /// [`RequestId`] must be passed back to [`Stream::reply`].
+    pub fn recv_request(&mut self) -> std::io::Result<Option<(RequestId, Msg)>> {
+        match Msg::read(&mut self.inner, self.max_msg_len) {
+            Ok(req) => Ok(Some(req)),
+            Err(e) if is_no_message(e.kind()) => Ok(None),
+            Err(e) => {
+                log::error!("Stream::recv_request error: {e}");
+                Err(e)
+            }
+        }
+    }
+    /// Send a reply to the request identified by `req_id`, as obtained from
+    /// [`Stream::recv_request`].
+    pub fn reply(&mut self, req_id: RequestId, msg: Msg) -> std::io::Result<()> {
+        msg.write(&mut self.inner, req_id)
+    }
+}
+
+/// Whether `kind` means "no message arrived" rather than a real I/O error.
+fn is_no_message(kind: ErrorKind) -> bool {
+    matches!(
+        kind,
+        ErrorKind::UnexpectedEof | ErrorKind::WouldBlock | ErrorKind::TimedOut
+    )
 }
 
 /// Connect to an existing instance, or establish self as the existing instance
 ///
 /// The id should be a string unique to your application that's valid as a file name.
 pub fn establish_endpoint(id: &str, nonblocking: bool) -> std::io::Result<Endpoint> {
+    establish_endpoint_with(id, &TransportConfig::LocalOnly, nonblocking)
+}
+
+/// Selects which transport [`establish_endpoint_with`] uses to coordinate
+/// instances.
+pub enum TransportConfig {
+    /// The default local-socket transport, same as [`establish_endpoint`].
+    /// Only coordinates instances on the same machine.
+    LocalOnly,
+    /// Coordinate over TCP at the given address instead, e.g. to reach an
+    /// instance on another host. Narrower "new instance" semantics than
+    /// [`TransportConfig::LocalOnly`]; see [`establish_endpoint_with`].
+    Tcp(std::net::SocketAddr),
+    /// Try the local-socket transport first, falling back to TCP at the
+    /// given address if a local socket can't be created or connected to.
+    LocalThenTcp(std::net::SocketAddr),
+}
+
+fn establish_local_endpoint(id: &str, nonblocking: bool) -> std::io::Result<Endpoint> {
     let ns_name = id.to_ns_name::<GenericNamespaced>()?;
     match local_socket::Stream::connect(ns_name.clone()) {
-        Ok(stream) => Ok(Endpoint::Existing(Stream(stream))),
+        Ok(stream) => Ok(Endpoint::Existing(Stream::new(RawStream::Local(stream)))),
         Err(e) => match e.kind() {
             ErrorKind::NotFound | ErrorKind::ConnectionRefused => {
                 let nb_mode = if nonblocking {
@@ -172,13 +449,82 @@ pub fn establish_endpoint(id: &str, nonblocking: bool) -> std::io::Result<Endpoi
                     .nonblocking(nb_mode)
                     .create_sync()?;
                 log::info!("Established new endpoint with name {ns_name:?}");
-                Ok(Endpoint::New(Listener(listener)))
+                Ok(Endpoint::New(Listener(RawListener::Local(listener))))
             }
             _ => Err(e),
         },
     }
 }
 
+/// Unlike [`establish_local_endpoint`], only `ConnectionRefused` means
+/// "become the new instance", and the connect-then-bind transition is a
+/// TOCTOU race between simultaneous new-instance attempts.
+fn establish_tcp_endpoint(
+    addr: std::net::SocketAddr,
+    nonblocking: bool,
+) -> std::io::Result<Endpoint> {
+    match TcpStream::connect(addr) {
+        Ok(stream) => Ok(Endpoint::Existing(Stream::new(RawStream::Tcp(stream)))),
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+            let listener = TcpListener::bind(addr)?;
+            listener.set_nonblocking(nonblocking)?;
+            log::info!("Established new endpoint at {addr}");
+            Ok(Endpoint::New(Listener(RawListener::Tcp(listener))))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Connect to an existing instance, or establish self as the existing
+/// instance, using the transport selected by `config`.
+///
+/// The id should be a string unique to your application that's valid as a file name.
+/// It's ignored when `config` is [`TransportConfig::Tcp`].
+///
+/// Over TCP, only a refused connection makes this the new instance, and the
+/// connect-then-bind step is not atomic: two instances starting at the same
+/// time can both fail to connect, and then one of them fails to bind.
+pub fn establish_endpoint_with(
+    id: &str,
+    config: &TransportConfig,
+    nonblocking: bool,
+) -> std::io::Result<Endpoint> {
+    match config {
+        TransportConfig::LocalOnly => establish_local_endpoint(id, nonblocking),
+        TransportConfig::Tcp(addr) => establish_tcp_endpoint(*addr, nonblocking),
+        TransportConfig::LocalThenTcp(addr) => match establish_local_endpoint(id, nonblocking) {
+            Ok(endpoint) => Ok(endpoint),
+            Err(e) => {
+                log::warn!("Local-socket transport failed ({e}), falling back to TCP");
+                establish_tcp_endpoint(*addr, nonblocking)
+            }
+        },
+    }
+}
+
+/// Connect to an existing instance and forward this process's command line
+/// arguments to it as a [`Msg::Args`], or establish self as the existing
+/// instance.
+///
+/// This covers the common single-instance pattern of forwarding the file
+/// paths a second invocation was opened with to the already-running
+/// instance, without having to hand-roll the argument serialization.
+///
+/// `nonblocking` is forwarded to [`establish_endpoint`] for the
+/// [`Endpoint::New`] case, same as calling it directly.
+pub fn forward_args(id: &str, nonblocking: bool) -> std::io::Result<Endpoint> {
+    let endpoint = establish_endpoint(id, nonblocking)?;
+    if let Endpoint::Existing(mut stream) = endpoint {
+        let args = std::env::args_os()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        stream.send(Msg::Args(args))?;
+        Ok(Endpoint::Existing(stream))
+    } else {
+        Ok(endpoint)
+    }
+}
+
 /// Try to wait to be the new instance
 /// with a configurable timeout and sleep interval between attempts.
 pub fn wait_to_be_new(
@@ -199,3 +545,88 @@ pub fn wait_to_be_new(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pair of connected `Stream`s over a loopback TCP connection, used as
+    /// a stand-in for a local socket pair in tests.
+    fn tcp_pair() -> (Stream, Stream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = TcpStream::connect(addr).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (
+            Stream::new(RawStream::Tcp(server)),
+            Stream::new(RawStream::Tcp(client)),
+        )
+    }
+
+    #[test]
+    fn round_trip_every_variant() {
+        let (mut a, mut b) = tcp_pair();
+
+        a.send(Msg::Num(42)).unwrap();
+        assert_eq!(b.recv().unwrap(), Some(Msg::Num(42)));
+
+        a.send(Msg::Bytes(vec![1, 2, 3])).unwrap();
+        assert_eq!(b.recv().unwrap(), Some(Msg::Bytes(vec![1, 2, 3])));
+
+        a.send(Msg::String("hello".to_string())).unwrap();
+        assert_eq!(b.recv().unwrap(), Some(Msg::String("hello".to_string())));
+
+        a.send(Msg::Nudge).unwrap();
+        assert_eq!(b.recv().unwrap(), Some(Msg::Nudge));
+
+        a.send(Msg::Args(vec!["a".to_string(), "b".to_string()]))
+            .unwrap();
+        assert_eq!(
+            b.recv().unwrap(),
+            Some(Msg::Args(vec!["a".to_string(), "b".to_string()]))
+        );
+    }
+
+    #[test]
+    fn resync_recovers_from_garbage_prefix() {
+        let (mut a, mut b) = tcp_pair();
+        // Bytes that don't contain SYNC_MAGIC, followed by a real frame.
+        a.inner.write_all(&[0xAA; 16]).unwrap();
+        a.send(Msg::Num(7)).unwrap();
+        assert_eq!(b.recv().unwrap(), Some(Msg::Num(7)));
+    }
+
+    #[test]
+    fn oversized_frame_is_rejected_and_resynced() {
+        let (mut a, mut b) = tcp_pair();
+        b.set_max_msg_len(4);
+
+        // Hand-write a Bytes frame claiming a length over max_msg_len.
+        a.inner.write_all(&SYNC_MAGIC).unwrap();
+        write_usize(0, &mut a.inner).unwrap();
+        write_u8(Msg::Bytes(Vec::new()).discriminant(), &mut a.inner).unwrap();
+        write_usize(1024, &mut a.inner).unwrap();
+
+        // Followed by a valid, small frame.
+        a.send(Msg::Bytes(vec![1, 2])).unwrap();
+
+        assert_eq!(b.recv().unwrap(), Some(Msg::Bytes(vec![1, 2])));
+    }
+
+    #[test]
+    fn request_discards_replies_for_other_requests() {
+        let (mut client, mut server) = tcp_pair();
+
+        let handle = std::thread::spawn(move || client.request(Msg::Num(1)).unwrap());
+
+        let (req_id, msg) = server.recv_request().unwrap().unwrap();
+        assert_eq!(msg, Msg::Num(1));
+
+        // A reply for an unrelated request id should be discarded...
+        Msg::Num(999).write(&mut server.inner, req_id + 1).unwrap();
+        // ...leaving the matching reply as the one request() returns.
+        server.reply(req_id, Msg::Num(2)).unwrap();
+
+        assert_eq!(handle.join().unwrap(), Msg::Num(2));
+    }
+}